@@ -1,8 +1,15 @@
-#![allow(dead_code)]
+use std::io;
+use std::path::Path;
 
-use grep2::matcher::Matcher;
-use grep2::printer::{JSON, JSONBuilder, Standard, StandardBuilder, Stats};
-use grep2::searcher::Searcher;
+use grep2::matcher::{Captures, Match, Matcher};
+#[cfg(feature = "pcre2")]
+use grep2::pcre2::{RegexCaptures as PCRE2Captures, RegexMatcher as PCRE2RegexMatcher};
+use grep2::printer::{
+    JSON, JSONBuilder, Standard, StandardBuilder, Stats, Summary,
+    SummaryBuilder, SummaryKind,
+};
+use grep2::regex::{RegexCaptures as RustRegexCaptures, RegexMatcher as RustRegexMatcher};
+use grep2::searcher::{BinaryDetection, MmapChoice, Searcher};
 use encoding_rs::Encoding;
 use termcolor::WriteColor;
 
@@ -11,7 +18,9 @@ use termcolor::WriteColor;
 /// at a very high level.
 #[derive(Clone, Debug)]
 struct Config {
+    binary_detection: BinaryDetection,
     encoding: Option<&'static Encoding>,
+    mmap: MmapChoice,
     output: Output,
     stats: bool,
 }
@@ -19,7 +28,9 @@ struct Config {
 impl Default for Config {
     fn default() -> Config {
         Config {
+            binary_detection: BinaryDetection::none(),
             encoding: None,
+            mmap: MmapChoice::auto(),
             output: Output::default(),
             stats: false,
         }
@@ -33,8 +44,18 @@ pub enum Output {
     Standard {
         /// A configured builder for constructing the standard printer.
         builder: StandardBuilder,
-        /// The format emitted by the printer.
-        kind: OutputKind,
+    },
+    /// A summary printer, which aggregates results instead of emitting
+    /// matches line-by-line.
+    ///
+    /// This is used for the counting, files-with(out)-matches and quiet
+    /// output modes, none of which have anything to do with the classic
+    /// grep-like format that the standard printer produces.
+    Summary {
+        /// A configured builder for constructing the summary printer.
+        builder: SummaryBuilder,
+        /// The summary format emitted by the printer.
+        kind: SummaryKind,
     },
     /// A JSON printer, which emits results in the JSON Lines format.
     ///
@@ -45,47 +66,9 @@ pub enum Output {
     },
 }
 
-/// The output mode for the standard printer.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub enum OutputKind {
-    /// The classic grep-like format.
-    Classic,
-    /// Show only a count of the total number of matches (counting each line
-    /// at most once) found.
-    ///
-    /// If the `path` setting is enabled, then the count is prefixed by the
-    /// corresponding file path.
-    Count,
-    /// Show only a count of the total number of matches (counting possibly
-    /// many matches on each line) found.
-    ///
-    /// If the `path` setting is enabled, then the count is prefixed by the
-    /// corresponding file path.
-    CountMatches,
-    /// Show only the file path if and only if a match was found.
-    ///
-    /// This ignores the `path` setting and always shows the file path.
-    FilesWithMatches,
-    /// Show only the file path if and only if a match was found.
-    ///
-    /// This ignores the `path` setting and always shows the file path.
-    FilesWithoutMatch,
-    /// Don't show any output and the stop the search once a match is found.
-    Quiet,
-}
-
 impl Default for Output {
     fn default() -> Output {
-        Output::Standard {
-            builder: StandardBuilder::new(),
-            kind: OutputKind::default(),
-        }
-    }
-}
-
-impl Default for OutputKind {
-    fn default() -> OutputKind {
-        OutputKind::Classic
+        Output::Standard { builder: StandardBuilder::new() }
     }
 }
 
@@ -122,6 +105,37 @@ impl SearchWorkerBuilder {
         SearchWorker { config, searcher, matcher, wtr, stats }
     }
 
+    /// Set the binary detection strategy to use for all searches.
+    ///
+    /// By default, binary detection is disabled, which means data is
+    /// searched as if it were text even when it isn't. Callers that want to
+    /// quit as soon as a NUL byte is seen, or have it converted to a line
+    /// terminator so line boundaries stay sane, should set this accordingly.
+    pub fn binary_detection(
+        &mut self,
+        detection: BinaryDetection,
+    ) -> &mut SearchWorkerBuilder {
+        self.config.binary_detection = detection;
+        self
+    }
+
+    /// Set the strategy used to decide whether a file is searched by
+    /// memory-mapping it or by reading it into a buffer.
+    ///
+    /// By default, an "auto" heuristic is used, which only memory-maps
+    /// regular files above a size threshold. Callers operating on
+    /// filesystems where memory maps are unsafe (network filesystems,
+    /// `/proc` and the like) should use `MmapChoice::never()` instead. Note
+    /// that this only applies to `search_path`; `search_reader` always
+    /// reads its input into a buffer since it has no file to map.
+    pub fn memory_map(
+        &mut self,
+        choice: MmapChoice,
+    ) -> &mut SearchWorkerBuilder {
+        self.config.mmap = choice;
+        self
+    }
+
     /// The type of encoding to use to read the source data. When this is set,
     /// the source data is transcoded from the specified encoding to UTF-8
     /// before being searched.
@@ -151,8 +165,147 @@ impl SearchWorkerBuilder {
         self.config.stats = yes;
         self
     }
+
+    /// Build a search worker driven by a `PatternMatcher`, which erases the
+    /// distinction between the default Rust regex engine and, when the
+    /// `pcre2` feature is enabled, a PCRE2-backed matcher.
+    ///
+    /// Unlike `build`, which is generic over `M: Matcher` and therefore
+    /// fixes the matcher type at compile time, this lets the caller pick
+    /// the concrete engine at runtime, e.g. by falling back to PCRE2 when a
+    /// pattern uses backreferences or look-around that the Rust engine
+    /// can't express.
+    pub fn build_dynamic<W>(
+        &self,
+        searcher: Searcher,
+        matcher: PatternMatcher,
+        wtr: W,
+    ) -> SearchWorker<PatternMatcher, W>
+    where W: WriteColor
+    {
+        self.build(searcher, matcher, wtr)
+    }
+}
+
+/// A matcher that is generic over the specific regex engine used to build
+/// it.
+///
+/// This allows a `SearchWorker` to be driven by either the default Rust
+/// regex engine or, when the `pcre2` feature is enabled, PCRE2, without the
+/// rest of ripgrep needing to be generic over which one was chosen.
+#[derive(Clone, Debug)]
+pub enum PatternMatcher {
+    RustRegex(RustRegexMatcher),
+    #[cfg(feature = "pcre2")]
+    PCRE2(PCRE2RegexMatcher),
+}
+
+impl Matcher for PatternMatcher {
+    type Captures = PatternCaptures;
+    type Error = PatternError;
+
+    fn find_at(
+        &self,
+        haystack: &[u8],
+        at: usize,
+    ) -> Result<Option<Match>, PatternError> {
+        use self::PatternMatcher::*;
+        match *self {
+            RustRegex(ref m) => {
+                m.find_at(haystack, at).map_err(PatternError::RustRegex)
+            }
+            #[cfg(feature = "pcre2")]
+            PCRE2(ref m) => {
+                m.find_at(haystack, at).map_err(PatternError::PCRE2)
+            }
+        }
+    }
+
+    fn new_captures(&self) -> Result<PatternCaptures, PatternError> {
+        use self::PatternMatcher::*;
+        match *self {
+            RustRegex(ref m) => {
+                m.new_captures()
+                    .map(PatternCaptures::RustRegex)
+                    .map_err(PatternError::RustRegex)
+            }
+            #[cfg(feature = "pcre2")]
+            PCRE2(ref m) => {
+                m.new_captures()
+                    .map(PatternCaptures::PCRE2)
+                    .map_err(PatternError::PCRE2)
+            }
+        }
+    }
+
+    fn capture_count(&self) -> usize {
+        use self::PatternMatcher::*;
+        match *self {
+            RustRegex(ref m) => m.capture_count(),
+            #[cfg(feature = "pcre2")]
+            PCRE2(ref m) => m.capture_count(),
+        }
+    }
+
+    fn capture_index(&self, name: &str) -> Option<usize> {
+        use self::PatternMatcher::*;
+        match *self {
+            RustRegex(ref m) => m.capture_index(name),
+            #[cfg(feature = "pcre2")]
+            PCRE2(ref m) => m.capture_index(name),
+        }
+    }
 }
 
+/// The set of capture groups produced by a `PatternMatcher`.
+#[derive(Clone, Debug)]
+pub enum PatternCaptures {
+    RustRegex(RustRegexCaptures),
+    #[cfg(feature = "pcre2")]
+    PCRE2(PCRE2Captures),
+}
+
+impl Captures for PatternCaptures {
+    fn len(&self) -> usize {
+        use self::PatternCaptures::*;
+        match *self {
+            RustRegex(ref c) => c.len(),
+            #[cfg(feature = "pcre2")]
+            PCRE2(ref c) => c.len(),
+        }
+    }
+
+    fn get(&self, i: usize) -> Option<Match> {
+        use self::PatternCaptures::*;
+        match *self {
+            RustRegex(ref c) => c.get(i),
+            #[cfg(feature = "pcre2")]
+            PCRE2(ref c) => c.get(i),
+        }
+    }
+}
+
+/// An error produced by the regex engine underlying a `PatternMatcher`.
+#[derive(Debug)]
+pub enum PatternError {
+    RustRegex(<RustRegexMatcher as Matcher>::Error),
+    #[cfg(feature = "pcre2")]
+    PCRE2(<PCRE2RegexMatcher as Matcher>::Error),
+}
+
+impl ::std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        use self::PatternError::*;
+        match *self {
+            RustRegex(ref err) => err.fmt(f),
+            #[cfg(feature = "pcre2")]
+            PCRE2(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl ::std::error::Error for PatternError {}
+
 #[derive(Debug)]
 pub struct SearchWorker<M, W> {
     config: Config,
@@ -162,7 +315,119 @@ pub struct SearchWorker<M, W> {
     stats: Option<Stats>,
 }
 
+/// The result of executing a search.
+///
+/// This reports whether a match was found and, if statistics were
+/// requested via `SearchWorkerBuilder::stats`, the aggregate statistics
+/// computed by the search.
+#[derive(Clone, Debug, Default)]
+pub struct SearchResult {
+    has_match: bool,
+    stats: Option<Stats>,
+}
+
+impl SearchResult {
+    /// Whether this search found a match or not.
+    pub fn has_match(&self) -> bool {
+        self.has_match
+    }
+
+    /// Return the search statistics for this result, if they were
+    /// requested.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+}
+
 impl<M: Matcher, W: WriteColor> SearchWorker<M, W> {
+    /// Return a reference to the statistics accumulated across every search
+    /// executed by this worker so far, if statistics were requested via
+    /// `SearchWorkerBuilder::stats`.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// Execute a search over the file system path provided.
+    pub fn search_path(&mut self, path: &Path) -> io::Result<SearchResult> {
+        self.prepare_searcher();
+        self.searcher.set_memory_map(self.config.mmap.clone());
+
+        let SearchWorker { ref mut searcher, ref matcher, ref mut wtr, ref mut stats, .. } = *self;
+        match *wtr {
+            Writer::Standard { ref mut printer } => {
+                let mut sink = printer.sink_with_path(matcher, path);
+                searcher.search_path(matcher, path, &mut sink)?;
+                Ok(SearchWorker::<M, W>::finish(stats, sink.has_match(), sink.stats()))
+            }
+            Writer::Summary { ref mut printer } => {
+                let mut sink = printer.sink_with_path(matcher, path);
+                searcher.search_path(matcher, path, &mut sink)?;
+                Ok(SearchWorker::<M, W>::finish(stats, sink.has_match(), sink.stats()))
+            }
+            Writer::JSON { ref mut printer } => {
+                let mut sink = printer.sink_with_path(matcher, path);
+                searcher.search_path(matcher, path, &mut sink)?;
+                Ok(SearchWorker::<M, W>::finish(stats, sink.has_match(), sink.stats()))
+            }
+        }
+    }
+
+    /// Execute a search over the given reader. The path is used only for
+    /// cosmetic purposes, e.g. to prefix matches or to populate the `path`
+    /// field of JSON output; it need not refer to a real file on disk.
+    pub fn search_reader<R: io::Read>(
+        &mut self,
+        path: &Path,
+        rdr: R,
+    ) -> io::Result<SearchResult> {
+        self.prepare_searcher();
+
+        let SearchWorker { ref mut searcher, ref matcher, ref mut wtr, ref mut stats, .. } = *self;
+        match *wtr {
+            Writer::Standard { ref mut printer } => {
+                let mut sink = printer.sink_with_path(matcher, path);
+                searcher.search_reader(matcher, rdr, &mut sink)?;
+                Ok(SearchWorker::<M, W>::finish(stats, sink.has_match(), sink.stats()))
+            }
+            Writer::Summary { ref mut printer } => {
+                let mut sink = printer.sink_with_path(matcher, path);
+                searcher.search_reader(matcher, rdr, &mut sink)?;
+                Ok(SearchWorker::<M, W>::finish(stats, sink.has_match(), sink.stats()))
+            }
+            Writer::JSON { ref mut printer } => {
+                let mut sink = printer.sink_with_path(matcher, path);
+                searcher.search_reader(matcher, rdr, &mut sink)?;
+                Ok(SearchWorker::<M, W>::finish(stats, sink.has_match(), sink.stats()))
+            }
+        }
+    }
+
+    /// Apply encoding and binary detection configuration to the underlying
+    /// searcher. This must run before every search, since the searcher is
+    /// reused across many files.
+    fn prepare_searcher(&mut self) {
+        if let Some(encoding) = self.config.encoding {
+            self.searcher.set_encoding(Some(encoding));
+        }
+        self.searcher.set_binary_detection(
+            self.config.binary_detection.clone(),
+        );
+    }
+
+    /// Fold a sink's per-search statistics into the worker's running total
+    /// (if statistics were requested) and build the result returned to the
+    /// caller.
+    fn finish(
+        stats: &mut Option<Stats>,
+        has_match: bool,
+        sink_stats: Option<&Stats>,
+    ) -> SearchResult {
+        if let (Some(total), Some(sink_stats)) = (stats.as_mut(), sink_stats) {
+            *total += sink_stats;
+        }
+        let stats = if stats.is_some() { sink_stats.cloned() } else { None };
+        SearchResult { has_match, stats }
+    }
 }
 
 /// The writer for a search worker.
@@ -174,8 +439,12 @@ enum Writer<W> {
     Standard {
         /// A printer, which can cheaply build implementations of Sink.
         printer: Standard<W>,
-        /// The format emitted by the printer.
-        kind: OutputKind,
+    },
+    /// A summary printer, which aggregates results instead of emitting
+    /// matches line-by-line.
+    Summary {
+        /// A printer, which can cheaply build implementations of Sink.
+        printer: Summary<W>,
     },
     /// A JSON printer, which emits results in the JSON Lines format.
     ///
@@ -189,11 +458,13 @@ enum Writer<W> {
 impl<W: WriteColor> Writer<W> {
     fn new(output: &Output, wtr: W) -> Writer<W> {
         match *output {
-            Output::Standard { ref builder, kind } => {
-                Writer::Standard {
-                    printer: builder.build(wtr),
-                    kind: kind,
-                }
+            Output::Standard { ref builder } => {
+                Writer::Standard { printer: builder.build(wtr) }
+            }
+            Output::Summary { ref builder, kind } => {
+                let mut builder = builder.clone();
+                builder.kind(kind);
+                Writer::Summary { printer: builder.build(wtr) }
             }
             Output::JSON { ref builder } => {
                 Writer::JSON {